@@ -1,7 +1,11 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use serde::ser::{Serialize, Serializer};
-use spotify::SpotifyHandler;
+use spotify::{NowPlaying, SpotifyHandler};
 use tokio::{sync::mpsc::Sender, time::sleep};
 use translate::TranslationHandler;
 use weather::WeatherHandler;
@@ -9,13 +13,26 @@ use weather::WeatherHandler;
 use crate::{
     bot::SendMsg,
     command_handler::CommandHandlerError,
-    db::{DBConn, DBConnError},
+    db::DBConn,
     twitch_api::TwitchApi,
 };
 
 use self::weather::WeatherError;
+use history::MessageHistory;
+#[cfg(feature = "metrics")]
+use metrics::MetricsRecorder;
+use regex::Regex;
+#[cfg(feature = "sentry")]
+use reporting::report_action_error;
+use title::{TitleError, TitleFetcher};
+pub mod history;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "sentry")]
+pub mod reporting;
 pub mod spotify;
 mod sys;
+pub mod title;
 pub mod translate;
 pub mod weather;
 
@@ -51,15 +68,24 @@ pub struct ActionHandler {
     weather_handler: WeatherHandler,
     spotify_handler: SpotifyHandler,
     translator: TranslationHandler,
+    title_fetcher: TitleFetcher,
+    message_history: Arc<MessageHistory>,
+    eval_vars: Arc<Mutex<HashMap<String, f64>>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<MetricsRecorder>>,
 }
 
 impl ActionHandler {
     pub fn new(db_conn: DBConn, twitch_api: TwitchApi) -> Self {
         let weather_handler = WeatherHandler::new(db_conn.get_openweathermap_api_key().unwrap());
         let translator = TranslationHandler::new();
+        let title_fetcher = TitleFetcher::new();
+        #[cfg(feature = "metrics")]
+        let metrics = db_conn.get_metrics_config().map(MetricsRecorder::spawn);
         let spotify_handler = SpotifyHandler::new(
             db_conn.get_spotify_cilent_id().unwrap(),
             db_conn.get_spotify_client_secret().unwrap(),
+            db_conn.clone(),
         );
 
         Self {
@@ -68,6 +94,11 @@ impl ActionHandler {
             weather_handler,
             translator,
             spotify_handler,
+            title_fetcher,
+            message_history: Arc::new(MessageHistory::new()),
+            eval_vars: Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "metrics")]
+            metrics,
         }
     }
 
@@ -76,14 +107,24 @@ impl ActionHandler {
         action: &str,
         args: &Vec<String>,
         channel: &str,
+        username: &str,
         msg_sender: Sender<SendMsg>,
     ) -> Result<Option<String>, CommandHandlerError> {
         println!("Executing action {} with arguments {:?}", action, args);
 
-        match action {
+        #[cfg(feature = "metrics")]
+        let started_at = std::time::Instant::now();
+
+        // The arms run inside an async block so that a `?` in one of them
+        // ends only the block and its error still reaches the code below.
+        let result = async { match action {
             "spotify" => Ok(Some(self.get_spotify(channel).await?)),
             "spotify.playlist" => Ok(Some(self.get_spotify_playlist(channel).await?)),
             "lastsong" => Ok(Some(self.get_spotify_last_song(channel).await?)),
+            "resolve" => Ok(match args.first() {
+                Some(link) => Some(self.spotify_handler.resolve(link).await?),
+                None => Some(String::from("link not specified")),
+            }),
             "hitman" => Ok(match args.first() {
                 Some(name) => Some(
                     self.hitman(channel, &name.replace('@', ""), msg_sender)
@@ -108,6 +149,18 @@ impl ActionHandler {
                 true => Some(String::from("location not specified")),
             }),
             "translate" => Ok(Some(self.translate(args.first().unwrap()).await?)),
+            "eval" => Ok(match args.is_empty() {
+                false => Some(self.eval(username, &args.join(" "))),
+                true => Some(String::from("expression not specified")),
+            }),
+            "title" => Ok(match args.first() {
+                Some(url) => Some(self.get_title(url).await),
+                None => Some(String::from("url not specified")),
+            }),
+            "sed" => Ok(match args.is_empty() {
+                false => Some(self.sed(channel, &args.join(" "))),
+                true => Some(String::from("expression not specified")),
+            }),
             "emoteonly" => match args.first().unwrap().parse::<u64>() {
                 Ok(duration) => {
                     self.emote_only(channel, duration, msg_sender).await;
@@ -136,59 +189,45 @@ impl ActionHandler {
                 "unknown action {}",
                 action
             ))),
+        } }.await;
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record(action, channel, started_at.elapsed());
+        }
+
+        #[cfg(feature = "sentry")]
+        match &result {
+            Err(e) => report_action_error(action, channel, args.len(), &format!("{:?}", e)),
+            Ok(Some(message)) if is_soft_error(message) => {
+                report_action_error(action, channel, args.len(), message)
+            }
+            _ => {}
         }
+
+        result
     }
 
     async fn get_spotify(&self, channel: &str) -> Result<String, CommandHandlerError> {
-        match self.db_conn.get_spotify_access_token(channel) {
-            Ok((access_token, _)) => {
-                match self.spotify_handler.get_current_song(&access_token).await? {
-                    Some(song) => Ok(song),
-                    None => Ok(String::from("no song is currently playing")),
-                }
-            }
-            Err(e) => match e {
-                DBConnError::NotFound => Ok(String::from("not configured for this channel")),
-                _ => Err(CommandHandlerError::DBError(e)),
-            },
+        match self.spotify_handler.get_current_song(channel).await? {
+            NowPlaying::Playing(song) => Ok(song),
+            NowPlaying::Idle => Ok(String::from("no song is currently playing")),
+            NowPlaying::NotConfigured => Ok(String::from("not configured for this channel")),
         }
     }
 
     async fn get_spotify_playlist(&self, channel: &str) -> Result<String, CommandHandlerError> {
-        match self.db_conn.get_spotify_access_token(channel) {
-            Ok((access_token, _)) => {
-                match self
-                    .spotify_handler
-                    .get_current_playlist(&access_token)
-                    .await?
-                {
-                    Some(playlist) => Ok(playlist),
-                    None => Ok(String::from("not currently playing a playlist")),
-                }
-            }
-            Err(e) => match e {
-                DBConnError::NotFound => Ok(String::from("not configured for this channel")),
-                _ => Err(CommandHandlerError::DBError(e)),
-            },
+        match self.spotify_handler.get_current_playlist(channel).await? {
+            NowPlaying::Playing(playlist) => Ok(playlist),
+            NowPlaying::Idle => Ok(String::from("not currently playing a playlist")),
+            NowPlaying::NotConfigured => Ok(String::from("not configured for this channel")),
         }
     }
 
     async fn get_spotify_last_song(&self, channel: &str) -> Result<String, CommandHandlerError> {
-        match self.db_conn.get_spotify_access_token(channel) {
-            Ok((access_token, _)) => {
-                match self
-                    .spotify_handler
-                    .get_recently_played(&access_token)
-                    .await
-                {
-                    Ok(recently_played) => Ok(recently_played),
-                    Err(e) => Ok(format!("error getting last song: {:?}", e)),
-                }
-            }
-            Err(e) => match e {
-                DBConnError::NotFound => Ok(String::from("not configured for this channel")),
-                _ => Err(CommandHandlerError::DBError(e)),
-            },
+        match self.spotify_handler.get_recently_played(channel).await {
+            Ok(recently_played) => Ok(recently_played),
+            Err(e) => Ok(format!("error getting last song: {:?}", e)),
         }
     }
 
@@ -311,4 +350,138 @@ impl ActionHandler {
             Err(e) => Ok(format!("error when translating: {:?}", e)),
         }
     }
+
+    /// Feeds a chat line into the rolling per-channel history used by `sed`.
+    /// The bot's message intake path should call this for every line, not
+    /// just ones that dispatch an action.
+    pub fn record_message(&self, channel: &str, username: &str, text: &str) {
+        self.message_history.record(channel, username, text);
+    }
+
+    fn sed(&self, channel: &str, expression: &str) -> String {
+        let (pattern, replacement, global, case_insensitive) = match parse_sed_expression(expression)
+        {
+            Some(parts) => parts,
+            None => return String::from("expected s/pattern/replacement/flags"),
+        };
+
+        let regex = match Regex::new(&format!(
+            "{}{}",
+            if case_insensitive { "(?i)" } else { "" },
+            pattern
+        )) {
+            Ok(regex) => regex,
+            Err(e) => return format!("invalid regex: {}", e),
+        };
+
+        let found = self
+            .message_history
+            .find_latest(channel, |text| regex.is_match(text));
+
+        match found {
+            Some(message) => {
+                let corrected = if global {
+                    regex.replace_all(&message.text, replacement.as_str())
+                } else {
+                    regex.replace(&message.text, replacement.as_str())
+                };
+
+                format!("{} meant: {}", message.username, corrected)
+            }
+            None => String::from("no matching message found"),
+        }
+    }
+
+    async fn get_title(&self, url: &str) -> String {
+        match self.title_fetcher.fetch_title(url).await {
+            Ok(title) => title,
+            Err(TitleError::InvalidUrl) => String::from("not a valid url"),
+            Err(TitleError::ForbiddenHost) => String::from("that host can't be fetched"),
+            Err(TitleError::NoTitle) => String::from("couldn't find a title for that page"),
+            Err(TitleError::RequestFailed(e)) => format!("couldn't fetch that page: {}", e),
+        }
+    }
+
+    fn eval(&self, username: &str, expression: &str) -> String {
+        let mut vars = self.eval_vars.lock().unwrap();
+        let x = *vars.get(username).unwrap_or(&0.0);
+
+        let mut ctx = meval::Context::new();
+        ctx.var("x", x);
+
+        match meval::eval_str_with_context(expression, &ctx) {
+            Ok(result) if result.is_finite() => {
+                vars.insert(username.to_owned(), result);
+                result.to_string()
+            }
+            Ok(result) => format!("result is not a number: {}", result),
+            Err(e) => format!("couldn't evaluate that: {}", e),
+        }
+    }
+}
+
+/// Detects the "soft error" chat strings that handlers like `translate`,
+/// `get_weather`, and `get_spotify_last_song` return instead of propagating
+/// a `CommandHandlerError`, so they can still be reported to Sentry.
+#[cfg(feature = "sentry")]
+fn is_soft_error(message: &str) -> bool {
+    message.starts_with("error ") || message.starts_with("Failed ")
+}
+
+/// Parses a `s/pattern/replacement/flags` expression, returning the pattern,
+/// replacement, and whether the `g`/`i` flags were set. Any delimiter other
+/// than `/` that appears unescaped is treated as part of the surrounding
+/// part, i.e. only `/` is supported as a delimiter. The pattern ends at the
+/// first `/` and the flags start after the last one, so the replacement may
+/// itself contain `/`.
+fn parse_sed_expression(expression: &str) -> Option<(String, String, bool, bool)> {
+    let rest = expression.strip_prefix("s/")?;
+    let (pattern, rest) = rest.split_once('/')?;
+    let (replacement, flags) = rest.rsplit_once('/')?;
+
+    Some((
+        pattern.to_owned(),
+        replacement.to_owned(),
+        flags.contains('g'),
+        flags.contains('i'),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sed_expression_plain() {
+        let (pattern, replacement, global, case_insensitive) =
+            parse_sed_expression("s/foo/bar/").unwrap();
+        assert_eq!(pattern, "foo");
+        assert_eq!(replacement, "bar");
+        assert!(!global);
+        assert!(!case_insensitive);
+    }
+
+    #[test]
+    fn parse_sed_expression_flags() {
+        let (_, _, global, case_insensitive) = parse_sed_expression("s/foo/bar/gi").unwrap();
+        assert!(global);
+        assert!(case_insensitive);
+    }
+
+    #[test]
+    fn parse_sed_expression_replacement_may_contain_slashes() {
+        let (pattern, replacement, _, _) = parse_sed_expression("s/a/b/c/g").unwrap();
+        assert_eq!(pattern, "a");
+        assert_eq!(replacement, "b/c");
+    }
+
+    #[test]
+    fn parse_sed_expression_rejects_missing_prefix() {
+        assert!(parse_sed_expression("foo/bar/").is_none());
+    }
+
+    #[test]
+    fn parse_sed_expression_rejects_too_few_parts() {
+        assert!(parse_sed_expression("s/foo").is_none());
+    }
 }
@@ -0,0 +1,74 @@
+use std::fmt;
+
+use tokio::sync::mpsc::Sender;
+
+use crate::{action_handler::ActionHandler, bot::SendMsg, db::DBConnError};
+
+#[derive(Debug)]
+pub enum CommandHandlerError {
+    ExecutionError(String),
+    DBError(DBConnError),
+}
+
+impl fmt::Display for CommandHandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandHandlerError::ExecutionError(msg) => write!(f, "{}", msg),
+            CommandHandlerError::DBError(e) => write!(f, "database error: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for CommandHandlerError {}
+
+impl From<DBConnError> for CommandHandlerError {
+    fn from(e: DBConnError) -> Self {
+        CommandHandlerError::DBError(e)
+    }
+}
+
+const COMMAND_PREFIX: char = '!';
+
+#[derive(Clone)]
+pub struct CommandHandler {
+    action_handler: ActionHandler,
+}
+
+impl CommandHandler {
+    pub fn new(action_handler: ActionHandler) -> Self {
+        Self { action_handler }
+    }
+
+    /// Entry point for every chat line the bot receives. Lines starting
+    /// with `COMMAND_PREFIX` are parsed into an action name and arguments
+    /// and dispatched to the `ActionHandler`, tagged with the invoking
+    /// user's name so per-user state (like `eval`) works. Every line is fed
+    /// into the rolling chat history for `sed` once dispatch has finished,
+    /// so a `!sed ...` invocation can't match against itself.
+    pub async fn handle_message(
+        &self,
+        channel: &str,
+        username: &str,
+        text: &str,
+        msg_sender: Sender<SendMsg>,
+    ) -> Result<Option<String>, CommandHandlerError> {
+        let Some(command) = text.strip_prefix(COMMAND_PREFIX) else {
+            self.action_handler.record_message(channel, username, text);
+            return Ok(None);
+        };
+
+        let mut parts = command.split_whitespace();
+        let Some(action) = parts.next() else {
+            self.action_handler.record_message(channel, username, text);
+            return Ok(None);
+        };
+        let args: Vec<String> = parts.map(String::from).collect();
+
+        let result = self
+            .action_handler
+            .run(action, &args, channel, username, msg_sender)
+            .await;
+        self.action_handler.record_message(channel, username, text);
+        result
+    }
+}
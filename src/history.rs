@@ -0,0 +1,55 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+const HISTORY_CAPACITY: usize = 50;
+
+#[derive(Clone, Debug)]
+pub struct ChatMessage {
+    pub username: String,
+    pub text: String,
+}
+
+/// Keeps the last `HISTORY_CAPACITY` chat messages per channel, so features
+/// like `sed` can look back at recent lines without hitting Twitch again.
+#[derive(Default)]
+pub struct MessageHistory {
+    channels: Mutex<HashMap<String, VecDeque<ChatMessage>>>,
+}
+
+impl MessageHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an incoming chat line. Should be called from the bot's
+    /// message intake path for every message, regardless of whether it's a
+    /// command.
+    pub fn record(&self, channel: &str, username: &str, text: &str) {
+        let mut channels = self.channels.lock().unwrap();
+        let history = channels.entry(channel.to_owned()).or_default();
+
+        if history.len() == HISTORY_CAPACITY {
+            history.pop_front();
+        }
+
+        history.push_back(ChatMessage {
+            username: username.to_owned(),
+            text: text.to_owned(),
+        });
+    }
+
+    /// Returns the most recent message in `channel` matching `predicate`,
+    /// walking from newest to oldest.
+    pub fn find_latest<F>(&self, channel: &str, mut predicate: F) -> Option<ChatMessage>
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(channel)?
+            .iter()
+            .rev()
+            .find(|m| predicate(&m.text))
+            .cloned()
+    }
+}
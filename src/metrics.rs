@@ -0,0 +1,189 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::Client;
+use tokio::time::interval;
+
+#[derive(Clone, Debug)]
+pub enum MetricsBackend {
+    /// Push to a Prometheus Pushgateway at the given base URL, under `job`.
+    PushgatewayHttp { url: String, job: String },
+    /// Write gauges/counters to a Redis server at the given connection URL.
+    Redis { url: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct MetricsConfig {
+    pub backend: MetricsBackend,
+    pub flush_interval: Duration,
+}
+
+#[derive(Default)]
+struct MetricsState {
+    action_counts: HashMap<String, u64>,
+    action_total_latency: HashMap<String, Duration>,
+    active_channels: HashSet<String>,
+}
+
+/// Records action dispatch counts, per-action latency, and active channels,
+/// and periodically flushes them to a Prometheus Pushgateway or Redis,
+/// depending on `MetricsConfig::backend`.
+pub struct MetricsRecorder {
+    state: Mutex<MetricsState>,
+    config: MetricsConfig,
+    http: Client,
+}
+
+impl MetricsRecorder {
+    /// Builds a recorder and spawns its background flush loop.
+    pub fn spawn(config: MetricsConfig) -> Arc<Self> {
+        let recorder = Arc::new(Self {
+            state: Mutex::new(MetricsState::default()),
+            config,
+            http: Client::new(),
+        });
+
+        let background = recorder.clone();
+        tokio::spawn(async move { background.flush_loop().await });
+
+        recorder
+    }
+
+    pub fn record(&self, action: &str, channel: &str, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        *state.action_counts.entry(action.to_owned()).or_insert(0) += 1;
+        *state
+            .action_total_latency
+            .entry(action.to_owned())
+            .or_insert(Duration::ZERO) += elapsed;
+        state.active_channels.insert(channel.to_owned());
+    }
+
+    async fn flush_loop(self: Arc<Self>) {
+        let mut ticker = interval(self.config.flush_interval);
+
+        loop {
+            ticker.tick().await;
+
+            if let Err(e) = self.flush().await {
+                println!("failed to flush metrics: {}", e);
+            }
+        }
+    }
+
+    async fn flush(&self) -> Result<(), String> {
+        let snapshot = {
+            let mut state = self.state.lock().unwrap();
+            let snapshot = (
+                state.action_counts.clone(),
+                state.action_total_latency.clone(),
+                state.active_channels.len(),
+            );
+            // active_channels tracks channels seen since the last flush, not
+            // since process start, so the gauge reflects who's actually
+            // active rather than growing forever.
+            state.active_channels.clear();
+            snapshot
+        };
+
+        match &self.config.backend {
+            MetricsBackend::PushgatewayHttp { url, job } => {
+                self.push_prometheus(url, job, snapshot).await
+            }
+            MetricsBackend::Redis { url } => self.push_redis(url, snapshot).await,
+        }
+    }
+
+    async fn push_prometheus(
+        &self,
+        url: &str,
+        job: &str,
+        (counts, latencies, active_channels): (
+            HashMap<String, u64>,
+            HashMap<String, Duration>,
+            usize,
+        ),
+    ) -> Result<(), String> {
+        let mut body = String::new();
+
+        for (action, count) in &counts {
+            body.push_str(&format!(
+                "foobot_action_total{{action=\"{}\"}} {}\n",
+                action, count
+            ));
+        }
+
+        for (action, total) in &latencies {
+            body.push_str(&format!(
+                "foobot_action_latency_seconds_sum{{action=\"{}\"}} {}\n",
+                action,
+                total.as_secs_f64()
+            ));
+            // Pair the sum with its sample count so downstream consumers can
+            // compute an average (or build a histogram) instead of only
+            // ever seeing the running total.
+            let count = counts.get(action).copied().unwrap_or(0);
+            body.push_str(&format!(
+                "foobot_action_latency_seconds_count{{action=\"{}\"}} {}\n",
+                action, count
+            ));
+        }
+
+        body.push_str(&format!("foobot_active_channels {}\n", active_channels));
+
+        self.http
+            .post(format!("{}/metrics/job/{}", url.trim_end_matches('/'), job))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("pushgateway request failed: {}", e))?;
+
+        Ok(())
+    }
+
+    async fn push_redis(
+        &self,
+        url: &str,
+        (counts, latencies, active_channels): (
+            HashMap<String, u64>,
+            HashMap<String, Duration>,
+            usize,
+        ),
+    ) -> Result<(), String> {
+        let client = redis::Client::open(url).map_err(|e| format!("invalid redis url: {}", e))?;
+        let mut conn = client
+            .get_async_connection()
+            .await
+            .map_err(|e| format!("redis connection failed: {}", e))?;
+
+        for (action, count) in &counts {
+            let _: () = redis::cmd("SET")
+                .arg(format!("foobot:metrics:action_count:{}", action))
+                .arg(count)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| format!("redis write failed: {}", e))?;
+        }
+
+        for (action, total) in &latencies {
+            let _: () = redis::cmd("SET")
+                .arg(format!("foobot:metrics:action_latency_seconds:{}", action))
+                .arg(total.as_secs_f64())
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| format!("redis write failed: {}", e))?;
+        }
+
+        let _: () = redis::cmd("SET")
+            .arg("foobot:metrics:active_channels")
+            .arg(active_channels)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| format!("redis write failed: {}", e))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use sentry::ClientInitGuard;
+
+/// Initializes the Sentry client. The returned guard must be held for the
+/// lifetime of the process (dropping it flushes pending events and disables
+/// reporting), so callers should bind it in `main` and let it live until
+/// shutdown.
+pub fn init(dsn: &str) -> ClientInitGuard {
+    sentry::init((
+        dsn.to_owned(),
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            ..Default::default()
+        },
+    ))
+}
+
+/// Reports a dispatch failure (a propagated `CommandHandlerError` or a
+/// "soft error" chat string) as a Sentry event tagged with the action name,
+/// channel, and argument count.
+pub fn report_action_error(action: &str, channel: &str, arg_count: usize, message: &str) {
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("action", action);
+            scope.set_tag("channel", channel);
+            scope.set_tag("arg_count", &arg_count.to_string());
+        },
+        || {
+            sentry::capture_message(message, sentry::Level::Error);
+        },
+    );
+}
@@ -0,0 +1,541 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use reqwest::Client;
+use rspotify::{
+    clients::BaseClient,
+    model::{AlbumId, EpisodeId, Id, PlayableItem, PlaylistId, TrackId},
+    ClientCredsSpotify, Credentials,
+};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::{
+    command_handler::CommandHandlerError,
+    db::{DBConn, DBConnError},
+};
+
+const API_BASE: &str = "https://api.spotify.com/v1";
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const STALE_THRESHOLD: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Default)]
+struct PlayerSnapshot {
+    current_song: Option<String>,
+    current_playlist: Option<String>,
+    last_played: Option<String>,
+    fetched_at: Option<Instant>,
+}
+
+#[derive(Clone)]
+pub struct SpotifyHandler {
+    client_id: String,
+    client_secret: String,
+    http: Client,
+    app_client: Arc<Mutex<Option<ClientCredsSpotify>>>,
+    db_conn: DBConn,
+    cache: Arc<Mutex<HashMap<String, PlayerSnapshot>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrentlyPlaying {
+    item: Option<PlayingItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayingItem {
+    name: String,
+    artists: Vec<Artist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artist {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrentlyPlayingContext {
+    context: Option<PlaybackContext>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaybackContext {
+    #[serde(rename = "type")]
+    kind: String,
+    uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaylistObject {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentlyPlayedResponse {
+    items: Vec<RecentlyPlayedItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentlyPlayedItem {
+    track: PlayingItem,
+}
+
+/// Outcome of a now-playing/playlist lookup. Kept distinct from a plain
+/// `Option<String>` so callers can tell "nothing is currently playing" apart
+/// from "this channel never linked Spotify", matching the message
+/// `get_recently_played` already gives for the latter.
+pub enum NowPlaying {
+    Playing(String),
+    Idle,
+    NotConfigured,
+}
+
+#[derive(Debug, PartialEq)]
+enum ResourceKind {
+    Track,
+    Album,
+    Playlist,
+    Episode,
+}
+
+impl SpotifyHandler {
+    /// Builds the handler and spawns the background task that keeps
+    /// `cache` fresh for every linked channel, replacing the old
+    /// on-demand-per-invocation API calls.
+    pub fn new(client_id: String, client_secret: String, db_conn: DBConn) -> Self {
+        let poll_interval = db_conn
+            .get_spotify_poll_interval()
+            .unwrap_or(DEFAULT_POLL_INTERVAL);
+
+        let handler = Self {
+            client_id,
+            client_secret,
+            http: Client::new(),
+            app_client: Arc::new(Mutex::new(None)),
+            db_conn,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        handler.clone().spawn_poller(poll_interval);
+
+        handler
+    }
+
+    fn spawn_poller(self, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+
+            loop {
+                ticker.tick().await;
+                self.poll_all_channels().await;
+            }
+        });
+    }
+
+    async fn poll_all_channels(&self) {
+        let channels = match self.db_conn.list_spotify_linked_channels() {
+            Ok(channels) => channels,
+            Err(_) => return,
+        };
+
+        for channel in channels {
+            self.poll_channel(&channel).await;
+        }
+    }
+
+    async fn poll_channel(&self, channel: &str) {
+        let access_token = match self.db_conn.get_spotify_access_token(channel) {
+            Ok((access_token, _)) => access_token,
+            Err(_) => return,
+        };
+
+        let (song, playlist, last_played) = tokio::join!(
+            fetch_current_song(&self.http, &access_token),
+            fetch_current_playlist(&self.http, &access_token),
+            fetch_recently_played(&self.http, &access_token),
+        );
+        let (song, playlist, last_played) = (song.ok(), playlist.ok(), last_played.ok());
+        let any_succeeded = song.is_some() || playlist.is_some() || last_played.is_some();
+
+        let mut cache = self.cache.lock().await;
+        let snapshot = cache.entry(channel.to_owned()).or_default();
+
+        if let Some(song) = song {
+            snapshot.current_song = song;
+        }
+        if let Some(playlist) = playlist {
+            snapshot.current_playlist = playlist;
+        }
+        if let Some(last_played) = last_played {
+            snapshot.last_played = Some(last_played);
+        }
+
+        // Only bump the timestamp when something actually came back, so a
+        // Spotify outage ages the snapshot instead of looking fresh forever.
+        if any_succeeded {
+            snapshot.fetched_at = Some(Instant::now());
+        }
+    }
+
+    /// Returns the cached now-playing song for `channel`, falling back to a
+    /// live fetch if the channel has no cache entry yet or it's older than
+    /// `STALE_THRESHOLD`.
+    pub async fn get_current_song(&self, channel: &str) -> Result<NowPlaying, CommandHandlerError> {
+        if let Some((song, age)) = self.cached(channel, |s| s.current_song.clone()).await {
+            return Ok(match song {
+                Some(song) => NowPlaying::Playing(annotate_staleness(song, age)),
+                None => NowPlaying::Idle,
+            });
+        }
+
+        match self.db_conn.get_spotify_access_token(channel) {
+            Ok((access_token, _)) => {
+                match fetch_current_song(&self.http, &access_token).await? {
+                    Some(song) => Ok(NowPlaying::Playing(song)),
+                    None => Ok(NowPlaying::Idle),
+                }
+            }
+            Err(DBConnError::NotFound) => Ok(NowPlaying::NotConfigured),
+            Err(e) => Err(CommandHandlerError::DBError(e)),
+        }
+    }
+
+    /// Returns the cached playlist name for `channel`, with the same
+    /// staleness/live-fetch behavior as [`Self::get_current_song`].
+    pub async fn get_current_playlist(
+        &self,
+        channel: &str,
+    ) -> Result<NowPlaying, CommandHandlerError> {
+        if let Some((playlist, age)) = self.cached(channel, |s| s.current_playlist.clone()).await {
+            return Ok(match playlist {
+                Some(playlist) => NowPlaying::Playing(annotate_staleness(playlist, age)),
+                None => NowPlaying::Idle,
+            });
+        }
+
+        match self.db_conn.get_spotify_access_token(channel) {
+            Ok((access_token, _)) => {
+                match fetch_current_playlist(&self.http, &access_token).await? {
+                    Some(playlist) => Ok(NowPlaying::Playing(playlist)),
+                    None => Ok(NowPlaying::Idle),
+                }
+            }
+            Err(DBConnError::NotFound) => Ok(NowPlaying::NotConfigured),
+            Err(e) => Err(CommandHandlerError::DBError(e)),
+        }
+    }
+
+    /// Returns the cached last-played track for `channel`.
+    pub async fn get_recently_played(&self, channel: &str) -> Result<String, CommandHandlerError> {
+        if let Some((last_played, age)) = self.cached(channel, |s| s.last_played.clone()).await {
+            return Ok(match last_played {
+                Some(last_played) => annotate_staleness(last_played, age),
+                None => String::from("no recently played songs found"),
+            });
+        }
+
+        match self.db_conn.get_spotify_access_token(channel) {
+            Ok((access_token, _)) => fetch_recently_played(&self.http, &access_token).await,
+            Err(DBConnError::NotFound) => Ok(String::from("not configured for this channel")),
+            Err(e) => Err(CommandHandlerError::DBError(e)),
+        }
+    }
+
+    /// Returns `None` when `channel` has no fresh cache entry yet (the
+    /// caller should fall back to a live fetch); `Some((value, age))`
+    /// otherwise, where `value` may itself be `None` if the channel is
+    /// known but genuinely has nothing to report.
+    async fn cached<F>(&self, channel: &str, extract: F) -> Option<(Option<String>, Duration)>
+    where
+        F: Fn(&PlayerSnapshot) -> Option<String>,
+    {
+        let cache = self.cache.lock().await;
+        let snapshot = cache.get(channel)?;
+        let age = snapshot.fetched_at?.elapsed();
+
+        if age > STALE_THRESHOLD {
+            return None;
+        }
+
+        Some((extract(snapshot), age))
+    }
+
+    /// Resolves a Spotify URL or URI (`spotify:track:...`,
+    /// `https://open.spotify.com/album/...`, playlist, episode) to a
+    /// human-readable description, without requiring the channel to have
+    /// linked an account.
+    pub async fn resolve(&self, link: &str) -> Result<String, CommandHandlerError> {
+        let (kind, id) = parse_spotify_link(link).ok_or_else(|| {
+            CommandHandlerError::ExecutionError(String::from("not a spotify link"))
+        })?;
+
+        let client = self.app_creds_client().await?;
+
+        match kind {
+            ResourceKind::Track => {
+                let track_id = TrackId::from_id(&id).map_err(|_| invalid_link())?;
+                let track = client.track(&track_id).await.map_err(|e| api_error(e))?;
+
+                let artists = track
+                    .artists
+                    .iter()
+                    .map(|a| a.name.clone())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let (min, sec) = duration_ms_to_min_sec(track.duration.num_milliseconds() as u64);
+
+                Ok(format!("{} - {} [{}:{:02}]", artists, track.name, min, sec))
+            }
+            ResourceKind::Album => {
+                let album_id = AlbumId::from_id(&id).map_err(|_| invalid_link())?;
+                let album = client.album(&album_id).await.map_err(|e| api_error(e))?;
+
+                let artists = album
+                    .artists
+                    .iter()
+                    .map(|a| a.name.clone())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                Ok(format!(
+                    "{} - {} ({} tracks)",
+                    artists, album.name, album.tracks.total
+                ))
+            }
+            ResourceKind::Playlist => {
+                let playlist_id = PlaylistId::from_id(&id).map_err(|_| invalid_link())?;
+                let playlist = client
+                    .playlist(&playlist_id, None, None)
+                    .await
+                    .map_err(|e| api_error(e))?;
+
+                Ok(format!(
+                    "{} by {} ({} tracks)",
+                    playlist.name,
+                    playlist.owner.display_name.unwrap_or_default(),
+                    playlist.tracks.total
+                ))
+            }
+            ResourceKind::Episode => {
+                let episode_id = EpisodeId::from_id(&id).map_err(|_| invalid_link())?;
+                let episode = client
+                    .get_an_episode(&episode_id, None)
+                    .await
+                    .map_err(|e| api_error(e))?;
+
+                let (min, sec) = duration_ms_to_min_sec(episode.duration.num_milliseconds() as u64);
+
+                Ok(format!(
+                    "{} - {} [{}:{:02}]",
+                    episode.show.name, episode.name, min, sec
+                ))
+            }
+        }
+    }
+
+    async fn app_creds_client(&self) -> Result<ClientCredsSpotify, CommandHandlerError> {
+        let mut guard = self.app_client.lock().await;
+
+        let needs_refresh = match &*guard {
+            Some(client) => client
+                .get_token()
+                .lock()
+                .await
+                .unwrap()
+                .as_ref()
+                .map(|t| t.is_expired())
+                .unwrap_or(true),
+            None => true,
+        };
+
+        if needs_refresh {
+            let creds = Credentials::new(&self.client_id, &self.client_secret);
+            let client = ClientCredsSpotify::new(creds);
+            client.request_token().await.map_err(|e| api_error(e))?;
+            *guard = Some(client);
+        }
+
+        Ok(guard.clone().unwrap())
+    }
+}
+
+async fn fetch_current_song(
+    http: &Client,
+    access_token: &str,
+) -> Result<Option<String>, CommandHandlerError> {
+    let resp: Option<CurrentlyPlaying> = get_json(
+        http,
+        &format!("{}/me/player/currently-playing", API_BASE),
+        access_token,
+    )
+    .await?;
+
+    Ok(resp.and_then(|r| r.item).map(|item| format_track(&item)))
+}
+
+async fn fetch_current_playlist(
+    http: &Client,
+    access_token: &str,
+) -> Result<Option<String>, CommandHandlerError> {
+    let resp: Option<CurrentlyPlayingContext> =
+        get_json(http, &format!("{}/me/player", API_BASE), access_token).await?;
+
+    let context = match resp.and_then(|r| r.context) {
+        Some(context) if context.kind == "playlist" => context,
+        _ => return Ok(None),
+    };
+
+    let playlist_id = context.uri.rsplit(':').next().unwrap_or_default();
+    let playlist: Option<PlaylistObject> = get_json(
+        http,
+        &format!("{}/playlists/{}", API_BASE, playlist_id),
+        access_token,
+    )
+    .await?;
+
+    Ok(playlist.map(|p| p.name))
+}
+
+async fn fetch_recently_played(
+    http: &Client,
+    access_token: &str,
+) -> Result<String, CommandHandlerError> {
+    let resp: Option<RecentlyPlayedResponse> = get_json(
+        http,
+        &format!("{}/me/player/recently-played?limit=1", API_BASE),
+        access_token,
+    )
+    .await?;
+
+    match resp.and_then(|r| r.items.into_iter().next()) {
+        Some(item) => Ok(format_track(&item.track)),
+        None => Ok(String::from("no recently played songs found")),
+    }
+}
+
+async fn get_json<T>(
+    http: &Client,
+    url: &str,
+    access_token: &str,
+) -> Result<Option<T>, CommandHandlerError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let resp = http
+        .get(url)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .map_err(|e| {
+            CommandHandlerError::ExecutionError(format!("spotify request failed: {}", e))
+        })?;
+
+    if resp.status().as_u16() == 204 || !resp.status().is_success() {
+        return Ok(None);
+    }
+
+    resp.json::<T>()
+        .await
+        .map(Some)
+        .map_err(|e| CommandHandlerError::ExecutionError(format!("spotify response error: {}", e)))
+}
+
+fn annotate_staleness(value: String, age: Duration) -> String {
+    if age < Duration::from_secs(2) {
+        value
+    } else {
+        format!("{} (updated {}s ago)", value, age.as_secs())
+    }
+}
+
+fn format_track(item: &PlayingItem) -> String {
+    let artists = item
+        .artists
+        .iter()
+        .map(|a| a.name.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("{} - {}", artists, item.name)
+}
+
+fn duration_ms_to_min_sec(duration_ms: u64) -> (u64, u64) {
+    let total_seconds = duration_ms / 1000;
+    (total_seconds / 60, total_seconds % 60)
+}
+
+fn parse_spotify_link(link: &str) -> Option<(ResourceKind, String)> {
+    if let Some(rest) = link.strip_prefix("spotify:") {
+        let mut parts = rest.split(':');
+        let kind = parts.next()?;
+        let id = parts.next()?;
+        return Some((resource_kind(kind)?, id.to_owned()));
+    }
+
+    if link.contains("open.spotify.com/") {
+        let path = link.split("open.spotify.com/").nth(1)?;
+        let mut segments = path.split(['/', '?']);
+        let kind = segments.next()?;
+        let id = segments.next()?;
+        return Some((resource_kind(kind)?, id.to_owned()));
+    }
+
+    None
+}
+
+fn resource_kind(kind: &str) -> Option<ResourceKind> {
+    match kind {
+        "track" => Some(ResourceKind::Track),
+        "album" => Some(ResourceKind::Album),
+        "playlist" => Some(ResourceKind::Playlist),
+        "episode" => Some(ResourceKind::Episode),
+        _ => None,
+    }
+}
+
+fn invalid_link() -> CommandHandlerError {
+    CommandHandlerError::ExecutionError(String::from("couldn't parse that spotify link"))
+}
+
+fn api_error<E: std::fmt::Debug>(e: E) -> CommandHandlerError {
+    CommandHandlerError::ExecutionError(format!("spotify api error: {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_spotify_link_uri() {
+        let (kind, id) = parse_spotify_link("spotify:track:3n3Ppam7vgaVa1iaRUc9Jw").unwrap();
+        assert_eq!(kind, ResourceKind::Track);
+        assert_eq!(id, "3n3Ppam7vgaVa1iaRUc9Jw");
+    }
+
+    #[test]
+    fn parse_spotify_link_web_url() {
+        let (kind, id) =
+            parse_spotify_link("https://open.spotify.com/album/4m2880jivSbbyEGAKfITCa?si=abc")
+                .unwrap();
+        assert_eq!(kind, ResourceKind::Album);
+        assert_eq!(id, "4m2880jivSbbyEGAKfITCa");
+    }
+
+    #[test]
+    fn parse_spotify_link_rejects_unrelated_url() {
+        assert!(parse_spotify_link("https://example.com/track/123").is_none());
+    }
+
+    #[test]
+    fn resource_kind_unknown_is_none() {
+        assert!(resource_kind("show").is_none());
+    }
+
+    #[test]
+    fn duration_ms_to_min_sec_rounds_down_to_seconds() {
+        assert_eq!(duration_ms_to_min_sec(125_500), (2, 5));
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use regex::Regex;
+use reqwest::Client;
+use tokio::net::lookup_host;
+
+const MAX_BODY_BYTES: usize = 512 * 1024;
+const MAX_TITLE_LEN: usize = 200;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REDIRECTS: u8 = 5;
+
+#[derive(Debug)]
+pub enum TitleError {
+    InvalidUrl,
+    ForbiddenHost,
+    RequestFailed(String),
+    NoTitle,
+}
+
+pub struct TitleFetcher;
+
+impl TitleFetcher {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Fetches the page at `url` and returns its decoded, whitespace-collapsed
+    /// `<title>`, truncated to a chat-friendly length.
+    pub async fn fetch_title(&self, url: &str) -> Result<String, TitleError> {
+        let mut current = url::Url::parse(url).map_err(|_| TitleError::InvalidUrl)?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            let ip = self.guard_url(&current).await?;
+            let http = pinned_client(&current, ip)?;
+
+            let resp = http
+                .get(current.clone())
+                .send()
+                .await
+                .map_err(|e| TitleError::RequestFailed(e.to_string()))?;
+
+            if resp.status().is_redirection() {
+                let location = resp
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or(TitleError::RequestFailed(String::from(
+                        "redirect with no location",
+                    )))?;
+
+                current = current
+                    .join(location)
+                    .map_err(|_| TitleError::RequestFailed(String::from("invalid redirect")))?;
+                continue;
+            }
+
+            if !resp.status().is_success() {
+                return Err(TitleError::RequestFailed(format!(
+                    "status {}",
+                    resp.status()
+                )));
+            }
+
+            let mut body = Vec::new();
+            let mut stream = resp;
+            while let Some(chunk) = stream
+                .chunk()
+                .await
+                .map_err(|e| TitleError::RequestFailed(e.to_string()))?
+            {
+                if body.len() + chunk.len() > MAX_BODY_BYTES {
+                    break;
+                }
+                body.extend_from_slice(&chunk);
+            }
+
+            let html = String::from_utf8_lossy(&body);
+            return extract_title(&html);
+        }
+
+        Err(TitleError::RequestFailed(String::from(
+            "too many redirects",
+        )))
+    }
+
+    /// Resolves and validates `url`'s host, returning the single IP the
+    /// actual request must connect to. Returning the already-validated
+    /// address (rather than just `Ok(())`) lets the caller pin the
+    /// connection to it instead of letting the HTTP client re-resolve the
+    /// host — otherwise a short-TTL DNS record could answer public here and
+    /// private/loopback at connect time (DNS rebinding).
+    async fn guard_url(&self, url: &url::Url) -> Result<IpAddr, TitleError> {
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(TitleError::ForbiddenHost);
+        }
+
+        let host = url.host_str().ok_or(TitleError::InvalidUrl)?;
+        guard_against_private_host(host, url.port_or_known_default().unwrap_or(443)).await
+    }
+}
+
+async fn guard_against_private_host(host: &str, port: u16) -> Result<IpAddr, TitleError> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        check_ip(ip)?;
+        return Ok(ip);
+    }
+
+    let addrs = lookup_host((host, port))
+        .await
+        .map_err(|e| TitleError::RequestFailed(e.to_string()))?;
+
+    let mut first = None;
+    for addr in addrs {
+        check_ip(addr.ip())?;
+        first.get_or_insert(addr.ip());
+    }
+
+    first.ok_or_else(|| TitleError::RequestFailed(String::from("host has no addresses")))
+}
+
+/// Builds a one-shot client whose connection for `url`'s host is pinned to
+/// `ip`, so the request can't re-resolve to a different (unvalidated)
+/// address than the one `guard_against_private_host` just checked.
+fn pinned_client(url: &url::Url, ip: IpAddr) -> Result<Client, TitleError> {
+    let host = url.host_str().ok_or(TitleError::InvalidUrl)?;
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        // Redirects are followed manually so each hop can be re-validated
+        // against the private-host guard above.
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve(host, SocketAddr::new(ip, port))
+        .build()
+        .map_err(|e| TitleError::RequestFailed(e.to_string()))
+}
+
+fn check_ip(ip: IpAddr) -> Result<(), TitleError> {
+    let forbidden = match ip {
+        IpAddr::V4(v4) => is_forbidden_v4(v4),
+        IpAddr::V6(v6) => {
+            let segments = v6.segments();
+            let is_unique_local = segments[0] & 0xfe00 == 0xfc00;
+            let is_link_local = segments[0] & 0xffc0 == 0xfe80;
+
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || is_unique_local
+                || is_link_local
+                || v6.to_ipv4_mapped().is_some_and(is_forbidden_v4)
+        }
+    };
+
+    if forbidden {
+        Err(TitleError::ForbiddenHost)
+    } else {
+        Ok(())
+    }
+}
+
+fn is_forbidden_v4(v4: std::net::Ipv4Addr) -> bool {
+    v4.is_loopback()
+        || v4.is_private()
+        || v4.is_link_local()
+        || v4.is_unspecified()
+        || v4.is_broadcast()
+}
+
+fn extract_title(html: &str) -> Result<String, TitleError> {
+    let re = Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap();
+    let raw = re
+        .captures(html)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str())
+        .ok_or(TitleError::NoTitle)?;
+
+    let decoded = decode_entities(raw);
+    let collapsed = decoded.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed.trim();
+
+    if trimmed.is_empty() {
+        return Err(TitleError::NoTitle);
+    }
+
+    Ok(truncate(trimmed, MAX_TITLE_LEN))
+}
+
+fn decode_entities(text: &str) -> String {
+    let re = Regex::new(r"&(#x?[0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+    re.replace_all(text, |caps: &regex::Captures| {
+        let entity = &caps[1];
+
+        if let Some(hex) = entity
+            .strip_prefix("#x")
+            .or_else(|| entity.strip_prefix("#X"))
+        {
+            return u32::from_str_radix(hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .map(String::from)
+                .unwrap_or_else(|| caps[0].to_string());
+        }
+
+        if let Some(dec) = entity.strip_prefix('#') {
+            return dec
+                .parse::<u32>()
+                .ok()
+                .and_then(char::from_u32)
+                .map(String::from)
+                .unwrap_or_else(|| caps[0].to_string());
+        }
+
+        match entity {
+            "amp" => "&".to_string(),
+            "lt" => "<".to_string(),
+            "gt" => ">".to_string(),
+            "quot" => "\"".to_string(),
+            "apos" => "'".to_string(),
+            "nbsp" => " ".to_string(),
+            _ => caps[0].to_string(),
+        }
+    })
+    .into_owned()
+}
+
+fn truncate(text: &str, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        return text.to_owned();
+    }
+
+    let mut truncated: String = text.chars().take(max_len).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_ip_allows_public_v4() {
+        assert!(check_ip("93.184.216.34".parse().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn check_ip_rejects_loopback_v4() {
+        assert!(check_ip("127.0.0.1".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn check_ip_rejects_private_v4_ranges() {
+        for ip in ["10.0.0.1", "172.16.0.1", "192.168.1.1", "169.254.169.254"] {
+            assert!(
+                check_ip(ip.parse().unwrap()).is_err(),
+                "{ip} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn check_ip_rejects_loopback_and_unique_local_v6() {
+        assert!(check_ip("::1".parse().unwrap()).is_err());
+        assert!(check_ip("fd00::1".parse().unwrap()).is_err());
+        assert!(check_ip("fe80::1".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn check_ip_rejects_ipv4_mapped_private_v6() {
+        assert!(check_ip("::ffff:127.0.0.1".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn check_ip_allows_public_v6() {
+        assert!(check_ip("2001:4860:4860::8888".parse().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn decode_entities_named_and_numeric() {
+        assert_eq!(decode_entities("Tom &amp; Jerry"), "Tom & Jerry");
+        assert_eq!(decode_entities("&#65;&#x42;"), "AB");
+    }
+
+    #[test]
+    fn extract_title_decodes_and_collapses_whitespace() {
+        let html = "<html><head><title>Hello\n  &amp;   World</title></head></html>";
+        assert_eq!(extract_title(html).unwrap(), "Hello & World");
+    }
+
+    #[test]
+    fn extract_title_missing_returns_no_title() {
+        assert!(matches!(
+            extract_title("<html><body>no title here</body></html>"),
+            Err(TitleError::NoTitle)
+        ));
+    }
+
+    #[test]
+    fn truncate_short_text_is_unchanged() {
+        assert_eq!(truncate("hello", 10), "hello");
+    }
+
+    #[test]
+    fn truncate_long_text_gets_ellipsis() {
+        assert_eq!(truncate("hello world", 5), "hello...");
+    }
+}